@@ -8,9 +8,10 @@
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt;
 use std::fmt::Debug;
 use std::fs::File;
-use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 
 use differential_datalog::program::RelId;
@@ -20,23 +21,33 @@ use differential_datalog::record::Record;
 use differential_datalog::DDlog;
 
 use crate::observe::Observable;
+use crate::resolver::Resolver;
+use crate::resolver::UnresolvedPolicy;
 use crate::schema::Addr;
 use crate::schema::Node;
 use crate::schema::NodeCfg;
+use crate::schema::NodeOpts;
 use crate::schema::RelCfg;
+use crate::schema::RunOpts;
 use crate::schema::Sink;
 use crate::schema::Source;
 use crate::schema::SysCfg;
+use crate::sinks::Csv as CsvSink;
 use crate::sinks::File as FileSink;
+use crate::sinks::Parquet as ParquetSink;
+use crate::sources::Csv as CsvSource;
 use crate::sources::File as FileSource;
+use crate::sources::Parquet as ParquetSource;
 use crate::tcp_channel::TcpReceiver;
 use crate::tcp_channel::TcpSender;
 use crate::txnmux::TxnMux;
 use crate::DDlogServer;
 
-/// A mapping from member address to relation IDs used for describing
-/// output relationships.
-pub type Outputs = BTreeMap<Addr, HashSet<RelId>>;
+/// A mapping from peer node to the relation IDs we need to stream to
+/// it. Kept in terms of the abstract `Node` rather than a resolved
+/// `Addr`, because resolution now happens lazily, at connection time,
+/// through a `Resolver` -- see `add_tcp_senders`.
+pub type Outputs = BTreeMap<Node, HashSet<RelId>>;
 /// A mapping from abstract nodes to actual members in the system.
 pub type Assignment = BTreeMap<Node, Addr>;
 
@@ -45,32 +56,19 @@ pub type Assignment = BTreeMap<Node, Addr>;
 /// In a nutshell, this function deduces a mapping from all relations on
 /// a node to other nodes that have relations that have this relation as
 /// input. Unfortunately doing so is rather costly, as we ultimately
-/// have to visit pretty much all relations in the assignment and check
-/// them.
-fn deduce_outputs(
-    addr: &Addr,
-    node_cfg: &NodeCfg,
-    sys_cfg: &SysCfg,
-    assignment: &Assignment,
-) -> Outputs {
+/// have to visit pretty much all relations in the system configuration
+/// and check them.
+fn deduce_outputs(own_node: &Node, node_cfg: &NodeCfg, sys_cfg: &SysCfg) -> Outputs {
     node_cfg.keys().fold(Outputs::new(), |mut outputs, rel| {
         sys_cfg
             .iter()
-            .filter_map(|(uuid, node_cfg)| {
-                assignment.get(uuid).and_then(|other_addr| {
-                    if other_addr != addr {
-                        Some((other_addr, node_cfg))
-                    } else {
-                        None
-                    }
-                })
-            })
-            .for_each(|(addr, node_cfg)| {
+            .filter(|(uuid, _)| *uuid != own_node)
+            .for_each(|(uuid, node_cfg)| {
                 node_cfg.values().for_each(|rel_cfgs| {
                     rel_cfgs.iter().for_each(|rel_cfg| match rel_cfg {
                         RelCfg::Input(input) => {
                             if input == rel {
-                                let rels = outputs.entry(addr.clone()).or_default();
+                                let rels = outputs.entry(*uuid).or_default();
                                 let _ = rels.insert(*input);
                             }
                         }
@@ -83,6 +81,83 @@ fn deduce_outputs(
     })
 }
 
+/// Compute the relation IDs present in `new` but not in `old`, per key.
+/// Keys that are entirely new show up with their full set of relation
+/// IDs; keys already known only contribute the relation IDs that were
+/// added. Used to diff `Outputs` (keyed by peer `Node`) as well as
+/// sink-/source-to-relations mappings (keyed by `&Sink`/`&Source`).
+fn rel_ids_added<K>(
+    old: &BTreeMap<K, HashSet<RelId>>,
+    new: &BTreeMap<K, HashSet<RelId>>,
+) -> BTreeMap<K, HashSet<RelId>>
+where
+    K: Ord + Clone,
+{
+    new.iter()
+        .filter_map(|(key, rel_ids)| {
+            let added = match old.get(key) {
+                Some(old_rel_ids) => rel_ids.difference(old_rel_ids).copied().collect(),
+                None => rel_ids.clone(),
+            };
+            if added.is_empty() {
+                None
+            } else {
+                Some((key.clone(), added))
+            }
+        })
+        .collect()
+}
+
+/// Check whether `new` dropped any key or relation ID that `old` had.
+fn rel_ids_removed<K>(old: &BTreeMap<K, HashSet<RelId>>, new: &BTreeMap<K, HashSet<RelId>>) -> bool
+where
+    K: Ord,
+{
+    old.iter().any(|(key, rel_ids)| match new.get(key) {
+        Some(new_rel_ids) => !rel_ids.is_subset(new_rel_ids),
+        None => !rel_ids.is_empty(),
+    })
+}
+
+/// Compute the output relations present in `new` but not in `old`, per
+/// peer node. Peers that are entirely new show up with their full set
+/// of relation IDs; peers already known only contribute the relation
+/// IDs that were added.
+fn outputs_added(old: &Outputs, new: &Outputs) -> Outputs {
+    rel_ids_added(old, new)
+}
+
+/// Check whether `new` dropped any peer or relation ID that `old` had.
+fn outputs_removed(old: &Outputs, new: &Outputs) -> bool {
+    rel_ids_removed(old, new)
+}
+
+/// Check whether `new_sinks` added relation IDs to a `Sink::File` path
+/// that `old_sinks` already had relation IDs open on.
+///
+/// Unlike `Csv`/`Parquet`, whose sinks derive a separate file per relid
+/// via `relation_path`, a `File` sink writes every relid sharing it to
+/// the exact same path. Subscribing a second `FileSink` for just the
+/// newly added relid would reopen that path with `File::create`,
+/// truncating it out from under the `FileSink` already writing the
+/// other relids there. There is no API to extend an already-subscribed
+/// `FileSink`'s relid set in place, so this case has to force a full
+/// rebuild instead of an incremental add, the same as a removal.
+fn file_sink_gained_rel_ids(
+    old_sinks: &BTreeMap<&Sink, HashSet<RelId>>,
+    new_sinks: &BTreeMap<&Sink, HashSet<RelId>>,
+) -> bool {
+    old_sinks.iter().any(|(sink, old_rel_ids)| {
+        if !matches!(sink, Sink::File(_)) {
+            return false;
+        }
+        match new_sinks.get(sink) {
+            Some(new_rel_ids) => !new_rel_ids.is_subset(old_rel_ids),
+            None => false,
+        }
+    })
+}
+
 /// Deduce the required redirections for a given input/output
 /// configuration.
 fn deduce_redirects(config: &NodeCfg) -> HashMap<RelId, RelId> {
@@ -101,16 +176,28 @@ fn deduce_redirects(config: &NodeCfg) -> HashMap<RelId, RelId> {
     })
 }
 
-/// Create a `DDlogServer` as per the given node configuration.
-fn create_server<P>(node_cfg: &NodeCfg) -> Result<DDlogServer<P>, String>
+/// Create a `DDlogServer` as per the given node configuration and run
+/// options.
+///
+/// The redirects baked into the returned server are handed back
+/// alongside it so that callers (in particular `Realization::reconfigure`)
+/// can later tell whether a new configuration would require different
+/// redirects than the ones the server was constructed with.
+fn create_server<P>(
+    node_cfg: &NodeCfg,
+    opts: &NodeOpts,
+) -> Result<(DDlogServer<P>, HashMap<RelId, RelId>), String>
 where
     P: Send + DDlog,
 {
+    if opts.workers == 0 {
+        return Err("NodeOpts.workers must be at least 1".to_string());
+    }
+
     let redirects = deduce_redirects(node_cfg);
-    // TODO: Should the number of workers be made configurable?
-    let program = P::run(2, false, |_, _: &Record, _| {})?;
+    let program = P::run(opts.workers, opts.store_snapshot, |_, _: &Record, _| {})?;
 
-    Ok(DDlogServer::new(program, redirects))
+    Ok((DDlogServer::new(program, redirects.clone()), redirects))
 }
 
 /// Create a transaction multiplexer wrapping the given server.
@@ -127,13 +214,42 @@ where
 }
 
 /// Add as many `TcpSender` objects as required given the provided node
-/// configuration.
-fn add_tcp_senders<P>(server: &mut DDlogServer<P>, outputs: Outputs) -> Result<(), String>
+/// configuration, resolving each peer `Node` to an `Addr` lazily
+/// through `resolver`.
+///
+/// A `Node` the `resolver` cannot currently resolve is handled as per
+/// `on_unresolved`: either skipped (with a warning) or treated as a
+/// hard error.
+fn add_tcp_senders<P>(
+    server: &mut DDlogServer<P>,
+    outputs: Outputs,
+    resolver: &dyn Resolver,
+    on_unresolved: UnresolvedPolicy,
+) -> Result<(), String>
 where
     P: DDlog,
 {
     // Create streams for the deduced output relations.
-    outputs.into_iter().try_for_each(|(addr, rel_ids)| {
+    outputs.into_iter().try_for_each(|(node, rel_ids)| {
+        let addr = match resolver.resolve(&node)? {
+            Some(addr) => addr,
+            None => {
+                return match on_unresolved {
+                    UnresolvedPolicy::Skip => {
+                        eprintln!(
+                            "warning: could not resolve address for node {}, skipping {} output relation(s)",
+                            node,
+                            rel_ids.len()
+                        );
+                        Ok(())
+                    }
+                    UnresolvedPolicy::Error => {
+                        Err(format!("could not resolve address for node {}", node))
+                    }
+                }
+            }
+        };
+
         let timeout = Duration::from_secs(30);
         let interval = Duration::from_millis(500);
         let sender = TcpSender::with_retry(&addr, timeout, interval)
@@ -162,31 +278,75 @@ where
     Ok(())
 }
 
-/// Deduce a mapping from file sink to a list of relation IDs for the
-/// given node configuration.
-fn deduce_sinks_or_sources(node_cfg: &NodeCfg, sinks: bool) -> BTreeMap<&Path, HashSet<RelId>> {
+/// Deduce a mapping from sink to the relation IDs whose committed
+/// records should be written to it.
+fn deduce_sinks(node_cfg: &NodeCfg) -> BTreeMap<&Sink, HashSet<RelId>> {
     node_cfg
         .iter()
         .fold(BTreeMap::new(), |map, (relid, rel_cfgs)| {
             rel_cfgs.iter().fold(map, |mut map, rel_cfg| {
-                match rel_cfg {
-                    RelCfg::Sink(sink) if sinks => match sink {
-                        Sink::File(path) => {
-                            let _ = map.entry(path).or_default().insert(*relid);
-                        }
-                    },
-                    RelCfg::Source(source) if !sinks => match source {
-                        Source::File(path) => {
-                            let _ = map.entry(path).or_default().insert(*relid);
-                        }
-                    },
-                    _ => (),
-                };
+                if let RelCfg::Sink(sink) = rel_cfg {
+                    let _ = map.entry(sink).or_default().insert(*relid);
+                }
+                map
+            })
+        })
+}
+
+/// Deduce a mapping from source to the relation IDs it feeds.
+fn deduce_sources(node_cfg: &NodeCfg) -> BTreeMap<&Source, HashSet<RelId>> {
+    node_cfg
+        .iter()
+        .fold(BTreeMap::new(), |map, (relid, rel_cfgs)| {
+            rel_cfgs.iter().fold(map, |mut map, rel_cfg| {
+                if let RelCfg::Source(source) = rel_cfg {
+                    let _ = map.entry(source).or_default().insert(*relid);
+                }
                 map
             })
         })
 }
 
+/// Add file sinks to the given server object, as per the deduced
+/// sink-to-relations mapping, constructing the sink implementation
+/// matching each `Sink` variant.
+fn add_file_sinks_for<P>(
+    server: &mut DDlogServer<P>,
+    sinks: BTreeMap<&Sink, HashSet<RelId>>,
+) -> Result<(), String>
+where
+    P: Send + DDlog + 'static,
+    P::Convert: Send,
+{
+    sinks.into_iter().try_for_each(|(sink, rel_ids)| {
+        let mut stream = server.add_stream(rel_ids);
+        match sink {
+            Sink::File(path) => {
+                let file = File::create(path)
+                    .map_err(|e| format!("failed to create file {}: {}", path.display(), e))?;
+                stream
+                    .subscribe(Box::new(FileSink::<P::Convert>::new(file)))
+                    .map_err(|_| {
+                        format!("failed to subscribe file sink {} to DDlogServer", path.display())
+                    })
+            }
+            Sink::Csv(path) => stream
+                .subscribe(Box::new(CsvSink::<P::Convert>::new(path.clone())))
+                .map_err(|_| {
+                    format!("failed to subscribe CSV sink {} to DDlogServer", path.display())
+                }),
+            Sink::Parquet(path) => stream
+                .subscribe(Box::new(ParquetSink::<P::Convert>::new(path.clone())))
+                .map_err(|_| {
+                    format!(
+                        "failed to subscribe Parquet sink {} to DDlogServer",
+                        path.display()
+                    )
+                }),
+        }
+    })
+}
+
 /// Add file sinks to the given server object, as per the node
 /// configuration.
 fn add_file_sinks<P>(server: &mut DDlogServer<P>, node_cfg: &NodeCfg) -> Result<(), String>
@@ -194,115 +354,309 @@ where
     P: Send + DDlog + 'static,
     P::Convert: Send,
 {
-    deduce_sinks_or_sources(node_cfg, true)
-        .iter()
-        .try_for_each(|(path, rel_ids)| {
-            let file = File::create(path)
-                .map_err(|e| format!("failed to create file {}: {}", path.display(), e))?;
-            let sink = FileSink::<P::Convert>::new(file);
-
-            let mut stream = server.add_stream(rel_ids.clone());
-            stream.subscribe(Box::new(sink)).map_err(|_| {
-                format!(
-                    "failed to subscribe file sink {} to DDlogServer",
-                    path.display()
-                )
-            })?;
-            Ok(())
-        })
+    add_file_sinks_for(server, deduce_sinks(node_cfg))
 }
 
-fn add_file_sources<P>(
+/// Add file sources to the given transaction multiplexer, as per the
+/// deduced source-to-relations mapping, constructing the source
+/// implementation matching each `Source` variant.
+fn add_file_sources_for<P>(
     txnmux: &mut TxnMux<Update<P::Value>, String>,
-    node_cfg: &NodeCfg,
+    sources: BTreeMap<&Source, HashSet<RelId>>,
 ) -> Result<(), String>
 where
     P: DDlog + 'static,
     P::Convert: Send,
 {
-    deduce_sinks_or_sources(node_cfg, false)
-        .iter()
-        .try_for_each(|(path, _rel_ids)| {
+    sources.into_iter().try_for_each(|(source, rel_ids)| match source {
+        Source::File(path) => {
             let source = FileSource::<P::Convert, _>::new(path);
             txnmux
                 .add_observable(Box::new(source))
-                .map_err(|_| format!("failed to add file source {} to TxnMux", path.display()))?;
-            Ok(())
-        })
+                .map_err(|_| format!("failed to add file source {} to TxnMux", path.display()))
+        }
+        Source::Csv(path) => rel_ids.iter().try_for_each(|relid| {
+            let source = CsvSource::<P::Convert, _>::new(*relid, path);
+            txnmux
+                .add_observable(Box::new(source))
+                .map_err(|_| format!("failed to add CSV source {} to TxnMux", path.display()))
+        }),
+        Source::Parquet(path) => rel_ids.iter().try_for_each(|relid| {
+            let source = ParquetSource::<P::Convert, _>::new(*relid, path);
+            txnmux
+                .add_observable(Box::new(source))
+                .map_err(|_| {
+                    format!("failed to add Parquet source {} to TxnMux", path.display())
+                })
+        }),
+    })
+}
+
+fn add_file_sources<P>(
+    txnmux: &mut TxnMux<Update<P::Value>, String>,
+    node_cfg: &NodeCfg,
+) -> Result<(), String>
+where
+    P: DDlog + 'static,
+    P::Convert: Send,
+{
+    add_file_sources_for::<P>(txnmux, deduce_sources(node_cfg))
 }
 
 /// Realize the given configuration locally.
-// TODO: Right now this function assumes a pristine state (i.e., nothing
-//       had been created previously), however we really would want to
-//       transition from a previously created state (which happens to be
-//       "empty" initially) to the given one.
 fn realize<P>(
+    own_node: &Node,
     addr: &Addr,
     node_cfg: &NodeCfg,
     outputs: Outputs,
-) -> Result<Realization<P::Value>, String>
+    resolver: &Arc<dyn Resolver>,
+    on_unresolved: UnresolvedPolicy,
+    opts: NodeOpts,
+) -> Result<Realization<P>, String>
 where
     P: Send + DDlog + 'static,
     P::Convert: Send,
 {
-    let mut server = create_server::<P>(&node_cfg)?;
-    add_tcp_senders(&mut server, outputs)?;
+    let (mut server, redirects) = create_server::<P>(&node_cfg, &opts)?;
+    add_tcp_senders(
+        &mut server,
+        outputs.clone(),
+        resolver.as_ref(),
+        on_unresolved,
+    )?;
     add_file_sinks(&mut server, node_cfg)?;
 
-    let mut txnmux = create_txn_mux(server)?;
+    // `DDlogServer` is a cheap, cloneable handle onto the shared program
+    // state, so we can hand one clone to the `TxnMux` (which drives it
+    // with incoming transactions) while keeping another around on the
+    // `Realization` itself, to be used by `reconfigure` for adding
+    // output streams and file sinks later on.
+    let mut txnmux = create_txn_mux(server.clone())?;
     add_tcp_receiver(&mut txnmux, addr)?;
     add_file_sources::<P>(&mut txnmux, node_cfg)?;
 
-    Ok(Realization { txnmux })
+    Ok(Realization {
+        own_node: *own_node,
+        addr: addr.clone(),
+        node_cfg: node_cfg.clone(),
+        outputs,
+        redirects,
+        resolver: resolver.clone(),
+        on_unresolved,
+        opts,
+        server,
+        txnmux,
+    })
 }
 
 /// An object representing a realized configuration.
 ///
-/// Right now all clients can do with object of this type is dropping
-/// them to tear everything down.
-#[derive(Debug)]
-pub struct Realization<V>
+/// Dropping a `Realization` tears everything it set up down. For a
+/// controlled transition from one configuration to another without
+/// tearing down unaffected parts, use `reconfigure`.
+pub struct Realization<P>
 where
-    V: Debug + Send,
+    P: Send + DDlog,
 {
+    /// The abstract node this realization was instantiated for. Kept
+    /// around so that `reconfigure` can look its `NodeCfg` back up
+    /// directly instead of re-resolving `addr` against `sys_cfg`, which
+    /// would pick an arbitrary match when multiple abstract nodes
+    /// resolve to the same `Addr`.
+    own_node: Node,
+    /// The address this realization was instantiated on.
+    addr: Addr,
+    /// The node configuration currently realized.
+    node_cfg: NodeCfg,
+    /// The output streams currently instantiated, keyed by peer.
+    outputs: Outputs,
+    /// The redirects the current `server` was constructed with.
+    redirects: HashMap<RelId, RelId>,
+    /// The resolver used to look up peer addresses, kept around so
+    /// that `reconfigure` can reuse it for newly added peers.
+    resolver: Arc<dyn Resolver>,
+    /// What to do about a peer `Node` the `resolver` cannot resolve.
+    on_unresolved: UnresolvedPolicy,
+    /// The run options (worker count, ...) the current `server` was
+    /// constructed with.
+    opts: NodeOpts,
+    /// A handle to the `DDlogServer` driving this realization.
+    server: DDlogServer<P>,
     /// The transaction multiplexer everything is registered to.
-    txnmux: TxnMux<Update<V>, String>,
+    txnmux: TxnMux<Update<P::Value>, String>,
+}
+
+impl<P> Debug for Realization<P>
+where
+    P: Send + DDlog,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Realization")
+            .field("own_node", &self.own_node)
+            .field("addr", &self.addr)
+            .field("outputs", &self.outputs)
+            .field("redirects", &self.redirects)
+            .finish()
+    }
+}
+
+impl<P> Realization<P>
+where
+    P: Send + DDlog + 'static,
+    P::Convert: Send,
+{
+    /// Transition this realization from the configuration it currently
+    /// represents to the one described by `sys_cfg`, applying only the
+    /// differences instead of tearing everything down and rebuilding it
+    /// from scratch. The node configuration to apply is looked up in
+    /// `sys_cfg` by this `Realization`'s own node UUID (fixed at
+    /// construction time), not by re-resolving `addr` -- multiple
+    /// abstract nodes can resolve to the same `Addr`, and re-resolving
+    /// would risk picking up a different node's `NodeCfg`. Peer
+    /// addresses continue to be looked up through the `Resolver` this
+    /// `Realization` was instantiated with.
+    ///
+    /// # Invariant
+    /// A `DDlogServer`'s redirects are derived from `node_cfg` once, in
+    /// `create_server`, and there currently is no way to update them on
+    /// a live server. If the new configuration would change this node's
+    /// redirects (i.e. which relation feeds which), this method rebuilds
+    /// the server -- and with it this whole `Realization` -- from
+    /// scratch rather than diffing it. Likewise, removing a `TcpSender`
+    /// or file sink/source that is no longer required is not yet
+    /// supported incrementally (there is no API to unsubscribe one from
+    /// a live `DDlogServer`/`TxnMux`), so a configuration that drops an
+    /// output, sink, or source also triggers a full rebuild. Only
+    /// additions -- new peers to stream output to, new file sinks, new
+    /// file sources -- are applied in place. A node's run options
+    /// (worker count, ...) can likewise only take effect on a freshly
+    /// started DDlog program, so a change there also triggers a full
+    /// rebuild.
+    pub fn reconfigure(
+        &mut self,
+        sys_cfg: &SysCfg,
+        addr: &Addr,
+        run_opts: &RunOpts,
+    ) -> Result<(), String> {
+        let node_cfg = sys_cfg.get(&self.own_node).ok_or_else(|| {
+            format!(
+                "no node {} (this realization's own node) in sys_cfg",
+                self.own_node
+            )
+        })?;
+
+        let opts = run_opts.get(&self.own_node).copied().unwrap_or_default();
+        let redirects = deduce_redirects(node_cfg);
+        let outputs = deduce_outputs(&self.own_node, node_cfg, sys_cfg);
+        let old_sinks = deduce_sinks(&self.node_cfg);
+        let new_sinks = deduce_sinks(node_cfg);
+        let old_sources = deduce_sources(&self.node_cfg);
+        let new_sources = deduce_sources(node_cfg);
+
+        let needs_rebuild = redirects != self.redirects
+            || opts != self.opts
+            || outputs_removed(&self.outputs, &outputs)
+            || rel_ids_removed(&old_sinks, &new_sinks)
+            || rel_ids_removed(&old_sources, &new_sources)
+            || file_sink_gained_rel_ids(&old_sinks, &new_sinks);
+
+        if needs_rebuild {
+            let own_node = self.own_node;
+            let resolver = self.resolver.clone();
+            let on_unresolved = self.on_unresolved;
+            *self = realize::<P>(&own_node, addr, node_cfg, outputs, &resolver, on_unresolved, opts)?;
+            return Ok(());
+        }
+
+        let added_outputs = outputs_added(&self.outputs, &outputs);
+        if !added_outputs.is_empty() {
+            add_tcp_senders(
+                &mut self.server,
+                added_outputs,
+                self.resolver.as_ref(),
+                self.on_unresolved,
+            )?;
+        }
+
+        let added_sinks = rel_ids_added(&old_sinks, &new_sinks);
+        if !added_sinks.is_empty() {
+            add_file_sinks_for(&mut self.server, added_sinks)?;
+        }
+
+        let added_sources = rel_ids_added(&old_sources, &new_sources);
+        if !added_sources.is_empty() {
+            add_file_sources_for::<P>(&mut self.txnmux, added_sources)?;
+        }
+
+        self.addr = addr.clone();
+        self.node_cfg = node_cfg.clone();
+        self.outputs = outputs;
+        Ok(())
+    }
+
+    /// Tear this realization down in a well-defined order: first the
+    /// `TxnMux` (detaching the `TcpReceiver` and any file sources, so no
+    /// further transactions arrive), then the `DDlogServer`. This is the
+    /// controlled teardown to use when a node is being drained from the
+    /// cluster; contrast with simply dropping a `Realization`, which
+    /// drops its fields in declaration order, i.e. `server` before
+    /// `txnmux`.
+    ///
+    /// The `Csv`/`File` sinks and `TcpSender` all flush after every
+    /// commit, so none of those leave anything buffered for this to
+    /// drain. A `Parquet` sink's per-relation writers are left open
+    /// across commits, but it finalizes them on `Drop`, so dropping
+    /// `server` here (and with it, the `Parquet` sinks it owns) closes
+    /// them out too.
+    ///
+    /// # Limitation
+    /// `DDlogServer` does not currently expose a fallible, per-component
+    /// teardown API, so this cannot collect and return errors the way a
+    /// sink's `on_commit` or `on_updates` would -- there simply isn't
+    /// anything for it to call that could fail. The `Result` is kept so
+    /// that a future, fallible teardown on `DDlogServer` can be wired in
+    /// without changing this method's signature.
+    pub fn shutdown(self) -> Result<(), String> {
+        let Realization { txnmux, server, .. } = self;
+        drop(txnmux);
+        drop(server);
+        Ok(())
+    }
 }
 
-/// Instantiate a configuration on a particular node under the given
-/// assignment.
+/// Instantiate a configuration on a particular node, resolving peer
+/// addresses lazily through `resolver` rather than requiring every
+/// node's address to be known up front. Each node runs with the
+/// `NodeOpts` `run_opts` assigns it, or `NodeOpts::default()` if absent.
 pub fn instantiate<P>(
     sys_cfg: SysCfg,
     addr: &Addr,
-    assignment: &Assignment,
-) -> Result<Vec<Realization<P::Value>>, String>
+    resolver: Arc<dyn Resolver>,
+    on_unresolved: UnresolvedPolicy,
+    run_opts: &RunOpts,
+) -> Result<Vec<Realization<P>>, String>
 where
     P: Send + DDlog + 'static,
     P::Convert: Send,
 {
-    assignment
-        .iter()
-        .filter_map(|(uuid, assigned_addr)| {
-            if assigned_addr == addr {
-                sys_cfg.get(uuid)
-            } else {
-                None
-            }
-        })
-        .try_fold(Vec::new(), |mut accumulator, node_cfg| {
-            // The supplied configuration by design does not
-            // include information about output streaming
-            // relations, because these can be inferred by
-            // looking at the input relations of other nodes.
-            // Start by doing exactly that such that we have
-            // enough information to fully configure a node
-            // locally.
-            let outputs = deduce_outputs(&addr, node_cfg, &sys_cfg, assignment);
-            realize::<P>(addr, node_cfg, outputs).map(|realization| {
-                accumulator.push(realization);
-                accumulator
-            })
-        })
+    let mut accumulator = Vec::new();
+    for (uuid, node_cfg) in sys_cfg.iter() {
+        if resolver.resolve(uuid)?.as_ref() != Some(addr) {
+            continue;
+        }
+
+        // The supplied configuration by design does not include
+        // information about output streaming relations, because these
+        // can be inferred by looking at the input relations of other
+        // nodes. Start by doing exactly that such that we have enough
+        // information to fully configure a node locally.
+        let outputs = deduce_outputs(uuid, node_cfg, &sys_cfg);
+        let opts = run_opts.get(uuid).copied().unwrap_or_default();
+        let realization =
+            realize::<P>(uuid, addr, node_cfg, outputs, &resolver, on_unresolved, opts)?;
+        accumulator.push(realization);
+    }
+    Ok(accumulator)
 }
 
 #[cfg(test)]
@@ -339,15 +693,19 @@ mod tests {
             },
         };
 
-        let sinks = deduce_sinks_or_sources(&node_cfg, true);
+        let sinks = deduce_sinks(&node_cfg);
         assert_eq!(sinks.len(), 2);
 
-        let rel_ids = sinks.get(Path::new("output_0_2.dump")).unwrap();
+        let rel_ids = sinks
+            .get(&Sink::File(PathBuf::from("output_0_2.dump")))
+            .unwrap();
         assert_eq!(rel_ids.len(), 2);
         assert!(rel_ids.contains(&0));
         assert!(rel_ids.contains(&2));
 
-        let rel_ids = sinks.get(Path::new("output_3.dump")).unwrap();
+        let rel_ids = sinks
+            .get(&Sink::File(PathBuf::from("output_3.dump")))
+            .unwrap();
         assert_eq!(rel_ids.len(), 1);
         assert!(rel_ids.contains(&3));
     }
@@ -356,8 +714,6 @@ mod tests {
     fn output_deduction_two_nodes() {
         let uuid0 = Uuid::new_v4();
         let uuid1 = Uuid::new_v4();
-        let node0 = Addr::Ip("127.0.0.1:1".parse().unwrap());
-        let node1 = Addr::Ip("127.0.0.1:2".parse().unwrap());
 
         let node0_cfg = btreemap! {
             0 => btreeset! {
@@ -373,18 +729,14 @@ mod tests {
             uuid0 => node0_cfg.clone(),
             uuid1 => node1_cfg.clone(),
         };
-        let assignment = btreemap! {
-            uuid0 => node0.clone(),
-            uuid1 => node1.clone(),
-        };
 
-        let outputs = deduce_outputs(&node0, &node0_cfg, &sys_cfg, &assignment);
+        let outputs = deduce_outputs(&uuid0, &node0_cfg, &sys_cfg);
         let expected = btreemap! {
-            node1.clone() => hashset! { 0 },
+            uuid1 => hashset! { 0 },
         };
         assert_eq!(outputs, expected);
 
-        let outputs = deduce_outputs(&node1, &node1_cfg, &sys_cfg, &assignment);
+        let outputs = deduce_outputs(&uuid1, &node1_cfg, &sys_cfg);
         assert_eq!(outputs, Outputs::new());
     }
 
@@ -393,9 +745,6 @@ mod tests {
         let uuid0 = Uuid::new_v4();
         let uuid1 = Uuid::new_v4();
         let uuid2 = Uuid::new_v4();
-        let node0 = Addr::Ip("127.0.0.1:1".parse().unwrap());
-        let node1 = Addr::Ip("127.0.0.1:2".parse().unwrap());
-        let node2 = Addr::Ip("127.0.0.1:3".parse().unwrap());
 
         let node0_cfg = btreemap! {
             0 => btreeset!{
@@ -427,26 +776,136 @@ mod tests {
             uuid2 => node2_cfg.clone(),
         };
 
-        let assignment = btreemap! {
-            uuid0 => node0.clone(),
-            uuid1 => node1.clone(),
-            uuid2 => node2.clone(),
-        };
-
-        let outputs = deduce_outputs(&node0, &node0_cfg, &sys_cfg, &assignment);
+        let outputs = deduce_outputs(&uuid0, &node0_cfg, &sys_cfg);
         let expected = btreemap! {
-            node2.clone() => hashset! { 1 },
+            uuid2 => hashset! { 1 },
         };
         assert_eq!(outputs, expected);
 
-        let outputs = deduce_outputs(&node1, &node1_cfg, &sys_cfg, &assignment);
+        let outputs = deduce_outputs(&uuid1, &node1_cfg, &sys_cfg);
         let expected = btreemap! {
-            node2.clone() => hashset! { 3 },
+            uuid2 => hashset! { 3 },
         };
         assert_eq!(outputs, expected);
 
-        let outputs = deduce_outputs(&node2, &node2_cfg, &sys_cfg, &assignment);
+        let outputs = deduce_outputs(&uuid2, &node2_cfg, &sys_cfg);
         let expected = btreemap! {};
         assert_eq!(outputs, expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn outputs_diffing() {
+        let uuid1 = Uuid::new_v4();
+        let uuid2 = Uuid::new_v4();
+
+        let old = btreemap! {
+            uuid1 => hashset! { 0, 1 },
+        };
+        let new = btreemap! {
+            uuid1 => hashset! { 0, 1, 2 },
+            uuid2 => hashset! { 3 },
+        };
+
+        let added = outputs_added(&old, &new);
+        let expected = btreemap! {
+            uuid1 => hashset! { 2 },
+            uuid2 => hashset! { 3 },
+        };
+        assert_eq!(added, expected);
+        assert!(!outputs_removed(&old, &new));
+
+        let shrunk = btreemap! {
+            uuid1 => hashset! { 0 },
+        };
+        assert!(outputs_removed(&old, &shrunk));
+    }
+
+    // `Realization::reconfigure` decides what to add/rebuild for sinks
+    // and sources by running `deduce_sinks`/`deduce_sources` through
+    // `rel_ids_added`/`rel_ids_removed` -- the very functions this test
+    // exercises directly, with the same "a relid is added to a sink
+    // path shared with another relid" scenario `file_sink_deduction`
+    // sets up. A test driving `reconfigure` itself would additionally
+    // require a `DDlogServer`/`TxnMux`/`DDlog` implementation, none of
+    // which live in this module; those are provided by the surrounding
+    // crate, so this sticks to the diffing logic that is actually ours
+    // to get right.
+    #[test]
+    fn reconfigure_sink_diffing_adds_new_relid_to_existing_path() {
+        let old_node_cfg = btreemap! {
+            0 => btreeset! {
+                RelCfg::Sink(Sink::Csv(PathBuf::from("output_0_2"))),
+            },
+            2 => btreeset! {
+                RelCfg::Sink(Sink::Csv(PathBuf::from("output_0_2"))),
+            },
+        };
+        let new_node_cfg = btreemap! {
+            0 => btreeset! {
+                RelCfg::Sink(Sink::Csv(PathBuf::from("output_0_2"))),
+            },
+            2 => btreeset! {
+                RelCfg::Sink(Sink::Csv(PathBuf::from("output_0_2"))),
+            },
+            5 => btreeset! {
+                RelCfg::Sink(Sink::Csv(PathBuf::from("output_0_2"))),
+            },
+        };
+
+        let old_sinks = deduce_sinks(&old_node_cfg);
+        let new_sinks = deduce_sinks(&new_node_cfg);
+
+        // The path didn't disappear, so this must not force a rebuild.
+        assert!(!rel_ids_removed(&old_sinks, &new_sinks));
+        // A `Csv` sink derives a separate file per relid, so growing
+        // its relid set on an already-known path is not the `File`-sink
+        // reopening hazard `file_sink_gained_rel_ids` guards against.
+        assert!(!file_sink_gained_rel_ids(&old_sinks, &new_sinks));
+
+        // The new relid on that already-known path must still show up
+        // as something to incrementally subscribe -- dropping it
+        // silently, because the *key* wasn't new, was the bug.
+        let added = rel_ids_added(&old_sinks, &new_sinks);
+        let sink = Sink::Csv(PathBuf::from("output_0_2"));
+        assert_eq!(added.get(&sink).unwrap(), &hashset! { 5 });
+    }
+
+    // A `File` sink, by contrast, writes every relid sharing it to the
+    // exact same path, so the same scenario must force a rebuild
+    // instead of an incremental add -- see `file_sink_gained_rel_ids`.
+    #[test]
+    fn reconfigure_forces_rebuild_when_file_sink_gains_relid_on_existing_path() {
+        let old_node_cfg = btreemap! {
+            0 => btreeset! {
+                RelCfg::Sink(Sink::File(PathBuf::from("output_0_2.dump"))),
+            },
+            2 => btreeset! {
+                RelCfg::Sink(Sink::File(PathBuf::from("output_0_2.dump"))),
+            },
+        };
+        let new_node_cfg = btreemap! {
+            0 => btreeset! {
+                RelCfg::Sink(Sink::File(PathBuf::from("output_0_2.dump"))),
+            },
+            2 => btreeset! {
+                RelCfg::Sink(Sink::File(PathBuf::from("output_0_2.dump"))),
+            },
+            5 => btreeset! {
+                RelCfg::Sink(Sink::File(PathBuf::from("output_0_2.dump"))),
+            },
+        };
+
+        let old_sinks = deduce_sinks(&old_node_cfg);
+        let new_sinks = deduce_sinks(&new_node_cfg);
+
+        // The path didn't disappear, so plain removal-based diffing
+        // alone would not catch this.
+        assert!(!rel_ids_removed(&old_sinks, &new_sinks));
+
+        // But relid 5 was added to a `Sink::File` path two other
+        // relids are already writing to -- subscribing a second
+        // `FileSink` for just that relid would reopen (and truncate)
+        // the file the existing one is still writing to.
+        assert!(file_sink_gained_rel_ids(&old_sinks, &new_sinks));
+    }
+}
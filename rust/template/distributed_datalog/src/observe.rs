@@ -0,0 +1,35 @@
+//! Generic observer/observable traits used to wire together the
+//! producers of a distributed computation's transactions (TCP and
+//! file sources, and the `TxnMux` that merges them) with its consumers
+//! (the `DDlogServer`, and TCP and file sinks).
+
+/// An object that can be notified about a stream of transactions, each
+/// made up of zero or more updates of type `T`, and about failures of
+/// type `E`.
+pub trait Observer<T, E>: Send {
+    /// A transaction is about to start.
+    fn on_start(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// The current transaction committed successfully.
+    fn on_commit(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Updates making up the current transaction.
+    fn on_updates<'a>(&mut self, updates: Box<dyn Iterator<Item = T> + 'a>) -> Result<(), E>;
+
+    /// The observable will not produce any more data.
+    fn on_completed(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// An object producing a stream of transactions that `Observer`s can
+/// subscribe to.
+pub trait Observable<T, E> {
+    /// Subscribe the given observer to this observable, so that it
+    /// starts receiving the transactions produced from here on.
+    fn subscribe(&mut self, observer: Box<dyn Observer<T, E>>) -> Result<(), E>;
+}
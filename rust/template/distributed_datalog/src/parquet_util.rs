@@ -0,0 +1,43 @@
+//! Small helper used by the Parquet source for reading a file written
+//! by `sinks::Parquet` back out as rows of named text columns.
+
+use std::fs::File;
+use std::path::Path;
+
+use parquet::file::reader::FileReader;
+use parquet::file::reader::SerializedFileReader;
+use parquet::record::RowAccessor;
+
+/// Read every row of the Parquet file at `path`, returning each one as
+/// its column names alongside the textual value of every column.
+pub fn read_rows(path: &Path) -> Result<Vec<(Vec<String>, Vec<String>)>, String> {
+    let file =
+        File::open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    let reader = SerializedFileReader::new(file)
+        .map_err(|e| format!("failed to open Parquet reader: {}", e))?;
+
+    let columns: Vec<String> = reader
+        .metadata()
+        .file_metadata()
+        .schema()
+        .get_fields()
+        .iter()
+        .map(|f| f.name().to_string())
+        .collect();
+
+    reader
+        .get_row_iter(None)
+        .map_err(|e| format!("failed to iterate Parquet rows: {}", e))?
+        .map(|row| {
+            let row = row.map_err(|e| format!("failed to read Parquet row: {}", e))?;
+            let values = (0..columns.len())
+                .map(|i| {
+                    row.get_string(i)
+                        .cloned()
+                        .map_err(|e| format!("failed to read Parquet column {}: {}", i, e))
+                })
+                .collect::<Result<Vec<String>, String>>()?;
+            Ok((columns.clone(), values))
+        })
+        .collect()
+}
@@ -0,0 +1,73 @@
+//! A pluggable mechanism for resolving an abstract node's `Addr` at
+//! connection time, instead of requiring every node's address to be
+//! known and fixed up front in a single, fully materialized
+//! `Assignment`.
+
+use std::collections::BTreeMap;
+
+use crate::schema::Addr;
+use crate::schema::Node;
+
+/// Looks up the `Addr` a `Node` is currently reachable at.
+///
+/// Resolution happens lazily, at connection time, rather than once up
+/// front when the topology is instantiated. That is what would let a
+/// networked implementation -- where nodes register their `Addr` with
+/// a small rendezvous/resolver endpoint at startup -- bring a cluster
+/// up without every member pre-agreeing on every peer's IP:port.
+///
+/// Implementations are free to cache resolved addresses internally;
+/// callers may invoke `resolve` repeatedly for the same `Node`.
+pub trait Resolver: Send + Sync {
+    /// Resolve `node` to the `Addr` it is currently reachable at, or
+    /// `None` if it cannot currently be resolved. An absent node is
+    /// not necessarily an error -- see `UnresolvedPolicy`.
+    fn resolve(&self, node: &Node) -> Result<Option<Addr>, String>;
+}
+
+/// What to do when a `Resolver` cannot currently resolve a `Node`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnresolvedPolicy {
+    /// Log a warning and carry on without that node.
+    Skip,
+    /// Treat it as a hard error.
+    Error,
+}
+
+/// A `Resolver` that looks nodes up in a fixed, fully materialized
+/// map, preserving the original "every address known up front"
+/// behavior.
+#[derive(Clone, Debug)]
+pub struct StaticResolver(BTreeMap<Node, Addr>);
+
+impl StaticResolver {
+    /// Create a new resolver backed by `assignment`.
+    pub fn new(assignment: BTreeMap<Node, Addr>) -> Self {
+        Self(assignment)
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(&self, node: &Node) -> Result<Option<Addr>, String> {
+        Ok(self.0.get(node).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use maplit::btreemap;
+
+    use uuid::Uuid;
+
+    #[test]
+    fn static_resolver_resolves_known_and_unknown_nodes() {
+        let node = Uuid::new_v4();
+        let addr = Addr::Ip("127.0.0.1:1234".parse().unwrap());
+        let resolver = StaticResolver::new(btreemap! { node => addr.clone() });
+
+        assert_eq!(resolver.resolve(&node).unwrap(), Some(addr));
+        assert_eq!(resolver.resolve(&Uuid::new_v4()).unwrap(), None);
+    }
+}
@@ -0,0 +1,209 @@
+//! Schema types describing a distributed DDlog computation: which
+//! relations live on which node, how each one is wired up via
+//! `Input`/`Source`/`Sink`, and which `Addr`ess every abstract node is
+//! assigned to.
+//!
+//! These types can be built up programmatically, as the `instantiate`
+//! tests do, or loaded wholesale from a config file via
+//! `from_reader`/`from_path`, so that the topology of a distributed
+//! computation can be described declaratively and shipped to every
+//! participating node.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use differential_datalog::program::RelId;
+
+/// The UUID identifying an abstract node in a `SysCfg`.
+pub type Node = Uuid;
+
+/// The address a node's `DDlogServer` can be reached at.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Addr {
+    /// A plain `host:port` TCP address.
+    Ip(SocketAddr),
+}
+
+impl fmt::Display for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Addr::Ip(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+/// A sink records written for a relation end up at.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Sink {
+    /// Dump committed records for the relation, in DDlog's textual
+    /// command format, to the file at the given path.
+    File(PathBuf),
+    /// Write committed records for the relation as rows of a CSV file
+    /// at the given path, one file per relation sharing this sink.
+    Csv(PathBuf),
+    /// Write committed records for the relation as rows of a Parquet
+    /// file at the given path, one file per relation sharing this
+    /// sink.
+    Parquet(PathBuf),
+}
+
+/// A source records fed into a relation come from.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Source {
+    /// Feed DDlog commands read from the file at the given path.
+    File(PathBuf),
+    /// Feed rows read from the CSV file at the given path, inserting
+    /// one record per row into the relation.
+    Csv(PathBuf),
+    /// Feed rows read from the Parquet file at the given path,
+    /// inserting one record per row into the relation.
+    Parquet(PathBuf),
+}
+
+/// A description of how a single relation on a node is wired up.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum RelCfg {
+    /// This relation is fed by the output of another node's relation.
+    Input(RelId),
+    /// This relation's committed records are written to a sink.
+    Sink(Sink),
+    /// This relation is fed by an external source.
+    Source(Source),
+}
+
+/// A node's local configuration: for every relation it hosts, the set
+/// of ways it is wired up.
+pub type NodeCfg = BTreeMap<RelId, BTreeSet<RelCfg>>;
+
+/// The configuration of an entire distributed computation: every
+/// abstract node's local configuration, keyed by its UUID.
+pub type SysCfg = BTreeMap<Node, NodeCfg>;
+
+/// Tuning knobs for running a node's DDlog program, independent of its
+/// relation wiring. These vary by the hardware a given node runs on,
+/// which is why they are kept separate from `NodeCfg` rather than
+/// baked into it, mirroring how `Assignment` keeps addresses separate
+/// from relation wiring.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NodeOpts {
+    /// The number of worker threads to run the DDlog program with.
+    pub workers: usize,
+    /// Whether the DDlog program should maintain a queryable snapshot
+    /// of every relation's current content, as opposed to running in a
+    /// purely streaming fashion. Corresponds to the second argument of
+    /// `DDlog::run`.
+    pub store_snapshot: bool,
+}
+
+impl Default for NodeOpts {
+    /// The options `create_server` used before they became
+    /// configurable: two workers, no stored snapshot.
+    fn default() -> Self {
+        Self {
+            workers: 2,
+            store_snapshot: false,
+        }
+    }
+}
+
+/// The `NodeOpts` to run every abstract node's DDlog program with,
+/// keyed by its UUID. A node absent from this map runs with
+/// `NodeOpts::default()`.
+pub type RunOpts = BTreeMap<Node, NodeOpts>;
+
+/// The full, self-contained description of a distributed computation:
+/// the relations and wiring on every node, together with the address
+/// every node is assigned to. This is what `from_reader`/`from_path`
+/// load, and it carries everything an operator needs to hand the same
+/// file to every participating node, each of which then calls
+/// `instantiate` with its own local `addr`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// The relations hosted on every node and how they are wired up.
+    pub sys_cfg: SysCfg,
+    /// The address every abstract node is assigned to.
+    pub assignment: BTreeMap<Node, Addr>,
+    /// The run options for every node. Nodes not present here run with
+    /// `NodeOpts::default()`, so existing configs without this field
+    /// keep working unchanged.
+    #[serde(default)]
+    pub run_opts: RunOpts,
+}
+
+/// Deserialize a `Config`, encoded as JSON, from the given reader.
+pub fn from_reader<R>(reader: R) -> Result<Config, String>
+where
+    R: Read,
+{
+    serde_json::from_reader(reader).map_err(|e| format!("failed to parse config: {}", e))
+}
+
+/// Load a `Config` from the file at the given path. See `from_reader`
+/// for the expected format.
+pub fn from_path<P>(path: P) -> Result<Config, String>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file =
+        File::open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    from_reader(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use maplit::btreemap;
+    use maplit::btreeset;
+
+    #[test]
+    fn config_round_trips_through_json() {
+        let uuid = Uuid::new_v4();
+        let addr = Addr::Ip("127.0.0.1:1234".parse().unwrap());
+        let config = Config {
+            sys_cfg: btreemap! {
+                uuid => btreemap! {
+                    0 => btreeset! {
+                        RelCfg::Sink(Sink::File(PathBuf::from("output.dump"))),
+                    },
+                },
+            },
+            assignment: btreemap! {
+                uuid => addr.clone(),
+            },
+            run_opts: btreemap! {
+                uuid => NodeOpts { workers: 4, store_snapshot: true },
+            },
+        };
+
+        let json = serde_json::to_vec(&config).unwrap();
+        let parsed = from_reader(&json[..]).unwrap();
+        assert_eq!(parsed.sys_cfg, config.sys_cfg);
+        assert_eq!(parsed.assignment, config.assignment);
+        assert_eq!(parsed.run_opts, config.run_opts);
+    }
+
+    #[test]
+    fn run_opts_default_when_missing() {
+        let uuid = Uuid::new_v4();
+        let addr = Addr::Ip("127.0.0.1:1234".parse().unwrap());
+        let json = serde_json::json!({
+            "sys_cfg": { uuid.to_string(): {} },
+            "assignment": { uuid.to_string(): addr },
+        });
+
+        let parsed = from_reader(json.to_string().as_bytes()).unwrap();
+        assert_eq!(parsed.run_opts, RunOpts::new());
+    }
+}
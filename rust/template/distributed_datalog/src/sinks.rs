@@ -0,0 +1,348 @@
+//! Sink implementations subscribed to a node's relations, writing out
+//! every committed record in one of the supported output formats.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File as StdFile;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::path::PathBuf;
+
+use parquet::file::writer::FileWriter;
+use parquet::file::writer::SerializedFileWriter;
+
+use differential_datalog::program::RelId;
+use differential_datalog::program::Update;
+use differential_datalog::record::Record;
+
+use crate::observe::Observer;
+
+/// A sink dumping every committed record for its relations to a single
+/// file, in DDlog's textual command format.
+pub struct File<C> {
+    file: StdFile,
+    _marker: PhantomData<C>,
+}
+
+impl<C> File<C> {
+    /// Create a new file sink writing to `file`.
+    pub fn new(file: StdFile) -> Self {
+        Self {
+            file,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C, V> Observer<Update<V>, String> for File<C>
+where
+    C: From<Update<V>> + Display,
+    V: Send,
+{
+    fn on_updates<'a>(
+        &mut self,
+        updates: Box<dyn Iterator<Item = Update<V>> + 'a>,
+    ) -> Result<(), String> {
+        for update in updates {
+            writeln!(self.file, "{};", C::from(update))
+                .map_err(|e| format!("failed to write update to file sink: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn on_commit(&mut self) -> Result<(), String> {
+        self.file
+            .flush()
+            .map_err(|e| format!("failed to flush file sink: {}", e))
+    }
+}
+
+/// Derive the per-relation output path for a columnar sink: relations
+/// sharing one `Sink::Csv`/`Sink::Parquet` path each get their own file
+/// (named after the relation ID), because their columns differ.
+fn relation_path(base: &Path, relid: RelId, extension: &str) -> PathBuf {
+    let mut path = base.to_path_buf();
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.set_file_name(format!("{}.{}.{}", stem, relid, extension));
+    path
+}
+
+/// Convert a committed record into the flat row of column values a
+/// columnar sink writes out. DDlog records are generally
+/// `Record::NamedStruct`s, whose fields become the row's columns; any
+/// other record shape is written out as a single "value" column using
+/// its DDlog textual representation.
+fn row(record: &Record) -> Vec<String> {
+    match record {
+        Record::NamedStruct(_, fields) => fields
+            .iter()
+            .map(|(_, value)| format!("{}", value))
+            .collect(),
+        other => vec![format!("{}", other)],
+    }
+}
+
+fn columns(record: &Record) -> Vec<String> {
+    match record {
+        Record::NamedStruct(_, fields) => fields.iter().map(|(name, _)| name.clone()).collect(),
+        _ => vec!["value".to_string()],
+    }
+}
+
+/// A sink writing the committed records of each of its relations to a
+/// typed CSV file of its own, batching up the rows of a transaction
+/// per relation before writing them out.
+pub struct Csv<C> {
+    base: PathBuf,
+    writers: BTreeMap<RelId, csv::Writer<StdFile>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Csv<C> {
+    /// Create a new CSV sink writing relation-specific files derived
+    /// from `base`.
+    pub fn new(base: PathBuf) -> Self {
+        Self {
+            base,
+            writers: BTreeMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get (creating and writing the header row of, if this is the
+    /// first time we see `relid`) the writer for `relid`.
+    fn writer_for(
+        &mut self,
+        relid: RelId,
+        header: &[String],
+    ) -> Result<&mut csv::Writer<StdFile>, String> {
+        if let std::collections::btree_map::Entry::Vacant(entry) = self.writers.entry(relid) {
+            let path = relation_path(&self.base, relid, "csv");
+            let file = StdFile::create(&path)
+                .map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+            let mut writer = csv::Writer::from_writer(file);
+            writer
+                .write_record(header)
+                .map_err(|e| format!("failed to write CSV header: {}", e))?;
+            let _ = entry.insert(writer);
+        }
+        Ok(self.writers.get_mut(&relid).unwrap())
+    }
+}
+
+impl<C, V> Observer<Update<V>, String> for Csv<C>
+where
+    C: From<Update<V>> + Into<(RelId, Record)>,
+    V: Send,
+{
+    fn on_updates<'a>(
+        &mut self,
+        updates: Box<dyn Iterator<Item = Update<V>> + 'a>,
+    ) -> Result<(), String> {
+        let mut batches: BTreeMap<RelId, Vec<Vec<String>>> = BTreeMap::new();
+        let mut headers: BTreeMap<RelId, Vec<String>> = BTreeMap::new();
+
+        for update in updates {
+            let (relid, record) = C::from(update).into();
+            headers.entry(relid).or_insert_with(|| columns(&record));
+            batches.entry(relid).or_default().push(row(&record));
+        }
+
+        for (relid, rows) in batches {
+            let header = headers.remove(&relid).unwrap_or_default();
+            let writer = self.writer_for(relid, &header)?;
+            for row in rows {
+                writer
+                    .write_record(&row)
+                    .map_err(|e| format!("failed to write CSV row: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_commit(&mut self) -> Result<(), String> {
+        self.writers
+            .values_mut()
+            .try_for_each(|w| w.flush())
+            .map_err(|e| format!("failed to flush CSV sink: {}", e))
+    }
+}
+
+/// Open a fresh Parquet file at `path`, writing its schema -- every
+/// column generically typed as UTF8 text, the DDlog textual
+/// representation of the corresponding field, since we only know a
+/// relation's column names, not their DDlog types, at this layer -- but
+/// no row groups yet. The returned writer is left open for
+/// `write_parquet_row_group` to append to across subsequent commits;
+/// the file isn't valid Parquet until the writer is `close`d.
+fn open_parquet_writer(
+    path: &Path,
+    columns: &[String],
+) -> Result<SerializedFileWriter<StdFile>, String> {
+    use parquet::basic::ConvertedType;
+    use parquet::basic::Repetition;
+    use parquet::basic::Type as PhysicalType;
+    use parquet::file::properties::WriterProperties;
+    use parquet::schema::types::Type;
+    use std::sync::Arc;
+
+    let mut fields = columns
+        .iter()
+        .map(|name| {
+            Arc::new(
+                Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+                    .with_repetition(Repetition::REQUIRED)
+                    .with_converted_type(ConvertedType::UTF8)
+                    .build()
+                    .unwrap(),
+            )
+        })
+        .collect();
+    let schema = Arc::new(
+        Type::group_type_builder("row")
+            .with_fields(&mut fields)
+            .build()
+            .map_err(|e| format!("failed to build Parquet schema: {}", e))?,
+    );
+
+    let file = StdFile::create(path)
+        .map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+    let props = Arc::new(WriterProperties::builder().build());
+    SerializedFileWriter::new(file, schema, props)
+        .map_err(|e| format!("failed to open Parquet writer: {}", e))
+}
+
+/// Append `rows` to `writer` as one new row group, leaving the writer
+/// open for further row groups -- and further commits -- to follow.
+fn write_parquet_row_group(
+    writer: &mut SerializedFileWriter<StdFile>,
+    columns: &[String],
+    rows: &[Vec<String>],
+) -> Result<(), String> {
+    use parquet::column::writer::ColumnWriter;
+    use parquet::data_type::ByteArray;
+
+    let mut row_group = writer
+        .next_row_group()
+        .map_err(|e| format!("failed to start Parquet row group: {}", e))?;
+
+    for col in 0..columns.len() {
+        if let Some(mut column_writer) = row_group
+            .next_column()
+            .map_err(|e| format!("failed to start Parquet column: {}", e))?
+        {
+            let values: Vec<ByteArray> = rows
+                .iter()
+                .map(|row| row[col].as_bytes().to_vec().into())
+                .collect();
+            if let ColumnWriter::ByteArrayColumnWriter(ref mut w) = column_writer {
+                w.write_batch(&values, None, None)
+                    .map_err(|e| format!("failed to write Parquet column: {}", e))?;
+            }
+            row_group
+                .close_column(column_writer)
+                .map_err(|e| format!("failed to close Parquet column: {}", e))?;
+        }
+    }
+
+    writer
+        .close_row_group(row_group)
+        .map_err(|e| format!("failed to close Parquet row group: {}", e))
+}
+
+/// A sink writing the committed records of each of its relations to a
+/// typed Parquet file of its own, appending the rows of every
+/// transaction as a new row group to that relation's still-open
+/// writer, so a later commit doesn't throw away what an earlier one
+/// wrote. The file isn't readable until its writer is closed out,
+/// finalizing its footer, which happens either when `on_completed`
+/// says there is no more data to come, or -- since whatever owns this
+/// sink may instead just drop it, e.g. on shutdown, without ever
+/// seeing an explicit "completed" signal -- on `Drop`.
+pub struct Parquet<C> {
+    base: PathBuf,
+    writers: BTreeMap<RelId, SerializedFileWriter<StdFile>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Parquet<C> {
+    /// Create a new Parquet sink writing relation-specific files
+    /// derived from `base`.
+    pub fn new(base: PathBuf) -> Self {
+        Self {
+            base,
+            writers: BTreeMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Close out every still-open writer, finalizing its footer so the
+    /// file it wrote becomes valid, readable Parquet. Every writer is
+    /// given a chance to close even if an earlier one fails, so one
+    /// relation's bad disk doesn't leave every other relation's
+    /// otherwise-fine file without a footer too; the first error
+    /// encountered, if any, is returned.
+    fn close_writers(&mut self) -> Result<(), String> {
+        let mut result = Ok(());
+        for (_, writer) in std::mem::take(&mut self.writers) {
+            let closed = writer
+                .close()
+                .map_err(|e| format!("failed to close Parquet file: {}", e));
+            result = result.and(closed);
+        }
+        result
+    }
+}
+
+impl<C, V> Observer<Update<V>, String> for Parquet<C>
+where
+    C: From<Update<V>> + Into<(RelId, Record)>,
+    V: Send,
+{
+    fn on_updates<'a>(
+        &mut self,
+        updates: Box<dyn Iterator<Item = Update<V>> + 'a>,
+    ) -> Result<(), String> {
+        let mut batches: BTreeMap<RelId, Vec<Record>> = BTreeMap::new();
+        for update in updates {
+            let (relid, record) = C::from(update).into();
+            batches.entry(relid).or_default().push(record);
+        }
+
+        for (relid, records) in batches {
+            if records.is_empty() {
+                continue;
+            }
+            let header = columns(&records[0]);
+            let rows: Vec<Vec<String>> = records.iter().map(row).collect();
+
+            if !self.writers.contains_key(&relid) {
+                let path = relation_path(&self.base, relid, "parquet");
+                let writer = open_parquet_writer(&path, &header)?;
+                let _ = self.writers.insert(relid, writer);
+            }
+            let writer = self.writers.get_mut(&relid).unwrap();
+            write_parquet_row_group(writer, &header, &rows)?;
+        }
+        Ok(())
+    }
+
+    fn on_completed(&mut self) -> Result<(), String> {
+        self.close_writers()
+    }
+}
+
+impl<C> Drop for Parquet<C> {
+    /// Finalize any writers `on_completed` never got a chance to close,
+    /// e.g. because this sink was dropped directly as part of a
+    /// shutdown or a configuration rebuild rather than told there was
+    /// no more data coming. Errors are swallowed, since `Drop` has
+    /// nowhere to report them to.
+    fn drop(&mut self) {
+        let _ = self.close_writers();
+    }
+}